@@ -3,6 +3,10 @@ extern crate lazy_static;
 #[macro_use]
 extern crate error_chain;
 extern crate regex;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(all(test, feature = "serde"))]
+extern crate serde_json;
 
 pub mod pep440 {
     mod errors {
@@ -12,22 +16,63 @@ pub mod pep440 {
                     description("unable to parse version string")
                     display("unable to parse version string: '{}'", v)
                 }
+                InvalidSpecifier(v: String) {
+                    description("unable to parse version specifier")
+                    display("unable to parse version specifier: '{}'", v)
+                }
             }
         }
     }
 
     use self::errors::*;
     use std::result;
+    use std::cmp::Ordering;
     use std::fmt::{self, Display, Formatter};
     use regex::{self, Captures, Regex};
 
-    #[derive(Debug, PartialEq)]
+    #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
     pub enum PreReleaseSegment {
         Alpha(u64),
         Beta(u64),
         ReleaseCandidate(u64),
     }
 
+    /// A single dot-separated component of a local version label.
+    ///
+    /// Per PEP 440, numeric segments are always greater than
+    /// alphanumeric ones when compared against each other.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum LocalSegment {
+        Numeric(u64),
+        Alpha(String),
+    }
+
+    impl PartialOrd for LocalSegment {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for LocalSegment {
+        fn cmp(&self, other: &Self) -> Ordering {
+            match (self, other) {
+                (&LocalSegment::Numeric(a), &LocalSegment::Numeric(b)) => a.cmp(&b),
+                (LocalSegment::Alpha(a), LocalSegment::Alpha(b)) => a.cmp(b),
+                (&LocalSegment::Numeric(_), &LocalSegment::Alpha(_)) => Ordering::Greater,
+                (&LocalSegment::Alpha(_), &LocalSegment::Numeric(_)) => Ordering::Less,
+            }
+        }
+    }
+
+    impl Display for LocalSegment {
+        fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+            match *self {
+                LocalSegment::Numeric(val) => write!(f, "{}", val),
+                LocalSegment::Alpha(ref val) => write!(f, "{}", val),
+            }
+        }
+    }
+
     #[derive(Debug)]
     pub struct Version {
         pub epoch: Option<u64>,
@@ -35,7 +80,7 @@ pub mod pep440 {
         pub pre_release: Option<PreReleaseSegment>,
         pub post_release: Option<u64>,
         pub dev_release: Option<u64>,
-        pub local_label: Option<String>,
+        pub local_label: Option<Vec<LocalSegment>>,
     }
 
     impl Version {
@@ -108,11 +153,21 @@ pub mod pep440 {
             };
 
             let local_label = captures.at(12).map(|val| {
-                val.chars()
+                let normalized: String = val.chars()
                     .map(|c| match c {
                         '_' | '-' => '.',
                         _ => c,
                     })
+                    .collect();
+
+                normalized.split('.')
+                    .map(|segment| if segment.chars().all(|c| c.is_ascii_digit()) {
+                        segment.parse()
+                            .map(LocalSegment::Numeric)
+                            .unwrap_or_else(|_| LocalSegment::Alpha(segment.to_string()))
+                    } else {
+                        LocalSegment::Alpha(segment.to_string())
+                    })
                     .collect()
             });
 
@@ -202,46 +257,140 @@ pub mod pep440 {
             }
 
             if let Some(ref val) = self.local_label {
-                write!(f, "+{}", val)?;
+                write!(f, "+")?;
+
+                let len = val.len();
+                for segment in &val[0..len - 1] {
+                    write!(f, "{}.", segment)?;
+                }
+                write!(f, "{}", val[len - 1])?;
             }
 
             Ok(())
         }
     }
 
-    use std::cmp::Ordering;
+    /// A value that additionally carries a negative and a positive infinity,
+    /// used to slot "absent" version segments into their correct place in
+    /// the PEP 440 ordering without special-casing every comparison site.
+    #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+    #[allow(clippy::enum_variant_names)]
+    enum Inf<T> {
+        NegInf,
+        Val(T),
+        PosInf,
+    }
+
+    impl Version {
+        /// The release segments with trailing zeroes stripped, so that
+        /// `1.0` and `1.0.0` compare equal.
+        fn release_key(&self) -> Vec<u64> {
+            let mut key = self.release.clone();
+            while key.len() > 1 && *key.last().unwrap() == 0 {
+                key.pop();
+            }
+            key
+        }
+
+        fn pre_key(&self) -> Inf<&PreReleaseSegment> {
+            match (&self.pre_release, self.dev_release, self.post_release) {
+                (None, Some(_), None) => Inf::NegInf,
+                (None, _, _) => Inf::PosInf,
+                (Some(pre), _, _) => Inf::Val(pre),
+            }
+        }
+
+        fn post_key(&self) -> Inf<u64> {
+            match self.post_release {
+                Some(val) => Inf::Val(val),
+                None => Inf::NegInf,
+            }
+        }
+
+        fn dev_key(&self) -> Inf<u64> {
+            match self.dev_release {
+                Some(val) => Inf::Val(val),
+                None => Inf::PosInf,
+            }
+        }
+
+        fn local_key(&self) -> Inf<&Vec<LocalSegment>> {
+            match self.local_label {
+                Some(ref val) => Inf::Val(val),
+                None => Inf::NegInf,
+            }
+        }
+
+        /// Whether this is a pre-release, i.e. it has a pre-release or a
+        /// development release segment.
+        pub fn is_prerelease(&self) -> bool {
+            self.pre_release.is_some() || self.dev_release.is_some()
+        }
+
+        /// Whether this is a post-release.
+        pub fn is_postrelease(&self) -> bool {
+            self.post_release.is_some()
+        }
+
+        /// Whether this is a development release.
+        pub fn is_devrelease(&self) -> bool {
+            self.dev_release.is_some()
+        }
+
+        /// The public version, i.e. this version without its local label.
+        pub fn public(&self) -> String {
+            let mut s = self.to_string();
+            if let Some(pos) = s.find('+') {
+                s.truncate(pos);
+            }
+            s
+        }
+
+        /// The `epoch!release` part of this version.
+        pub fn base_version(&self) -> String {
+            let mut s = String::new();
+            if let Some(epoch) = self.epoch {
+                s.push_str(&format!("{}!", epoch));
+            }
+
+            let len = self.release.len();
+            for val in &self.release[0..len - 1] {
+                s.push_str(&format!("{}.", val));
+            }
+            s.push_str(&format!("{}", self.release[len - 1]));
+
+            s
+        }
+    }
 
     impl Ord for Version {
         fn cmp(&self, other: &Self) -> Ordering {
-            use std::iter;
-
             let r = self.epoch.unwrap_or(0).cmp(&other.epoch.unwrap_or(0));
             if r != Ordering::Equal {
                 return r;
             }
 
-            if self.release.len() > other.release.len() {
-                for (s1, s2) in self.release
-                    .iter()
-                    .zip(other.release.iter().chain(iter::repeat(&0))) {
-                    let r = s1.cmp(s2);
-                    if r != Ordering::Equal {
-                        return r;
-                    }
-                }
-            } else {
-                for (s1, s2) in self.release
-                    .iter()
-                    .chain(iter::repeat(&0))
-                    .zip(other.release.iter()) {
-                    let r = s1.cmp(s2);
-                    if r != Ordering::Equal {
-                        return r;
-                    }
-                }
+            let r = self.release_key().cmp(&other.release_key());
+            if r != Ordering::Equal {
+                return r;
             }
 
-            Ordering::Equal
+            let r = self.pre_key().cmp(&other.pre_key());
+            if r != Ordering::Equal {
+                return r;
+            }
+
+            let r = self.post_key().cmp(&other.post_key());
+            if r != Ordering::Equal {
+                return r;
+            }
+
+            let r = self.dev_key().cmp(&other.dev_key());
+            if r != Ordering::Equal {
+                return r;
+            }
+
+            self.local_key().cmp(&other.local_key())
         }
     }
 
@@ -259,9 +408,208 @@ pub mod pep440 {
 
     impl Eq for Version {}
 
+    /// A single version specifier clause, e.g. `>=1.0` or `~=2.2`.
+    #[derive(Debug)]
+    pub enum Specifier {
+        Equal { version: Version, prefix: bool },
+        NotEqual { version: Version, prefix: bool },
+        LessThanEqual(Version),
+        GreaterThanEqual(Version),
+        LessThan(Version),
+        GreaterThan(Version),
+        Compatible(Version),
+        ArbitraryEqual(String),
+    }
+
+    impl Specifier {
+        pub fn parse(s: &str) -> Result<Specifier> {
+            lazy_static! {
+                static ref RE: result::Result<Regex, regex::Error> = Regex::new(
+                    r"(?x)
+                    ^\s*
+                    (===|==|!=|<=|>=|<|>|~=) # operator
+                    \s*
+                    (.+?) # version, with an optional trailing .*
+                    \s*$");
+            }
+
+            match *RE {
+                Ok(ref re) => {
+                    if let Some(captures) = re.captures(s) {
+                        let operator = captures.at(1).unwrap();
+                        let rest = captures.at(2).unwrap();
+                        Self::parse_helper(s, operator, rest)
+                    } else {
+                        bail!(ErrorKind::InvalidSpecifier(s.to_string()));
+                    }
+                }
+                _ => bail!("unable to create regex"),
+            }
+        }
+
+        fn parse_helper(s: &str, operator: &str, rest: &str) -> Result<Specifier> {
+            if operator == "===" {
+                return Ok(Specifier::ArbitraryEqual(rest.to_string()));
+            }
+
+            let prefix = (operator == "==" || operator == "!=") && rest.ends_with(".*");
+            let version_str = if prefix { &rest[0..rest.len() - 2] } else { rest };
+
+            let version = Version::parse(version_str)
+                .chain_err(|| format!("invalid version in specifier: '{}'", s))?;
+
+            match operator {
+                "==" => Ok(Specifier::Equal {
+                    version: version,
+                    prefix: prefix,
+                }),
+                "!=" => Ok(Specifier::NotEqual {
+                    version: version,
+                    prefix: prefix,
+                }),
+                "<=" => Ok(Specifier::LessThanEqual(version)),
+                ">=" => Ok(Specifier::GreaterThanEqual(version)),
+                "<" => Ok(Specifier::LessThan(version)),
+                ">" => Ok(Specifier::GreaterThan(version)),
+                "~=" => {
+                    if version.release.len() < 2 {
+                        bail!(ErrorKind::InvalidSpecifier(s.to_string()));
+                    }
+                    Ok(Specifier::Compatible(version))
+                }
+                _ => bail!(ErrorKind::InvalidSpecifier(s.to_string())),
+            }
+        }
+
+        /// Whether `candidate`'s release segments (zero-padded to the
+        /// length of `prefix`'s) start with `prefix`'s release segments.
+        fn release_prefix_matches(prefix: &Version, candidate: &Version) -> bool {
+            if prefix.epoch.unwrap_or(0) != candidate.epoch.unwrap_or(0) {
+                return false;
+            }
+
+            let len = prefix.release.len();
+            let mut candidate_release = candidate.release.clone();
+            while candidate_release.len() < len {
+                candidate_release.push(0);
+            }
+
+            candidate_release[0..len] == prefix.release[0..len]
+        }
+
+        /// The release prefix implied by a `~=` clause: everything but the
+        /// last release segment.
+        fn compatible_prefix(version: &Version) -> Version {
+            let len = version.release.len();
+            Version {
+                epoch: version.epoch,
+                release: version.release[0..len - 1].to_vec(),
+                pre_release: None,
+                post_release: None,
+                dev_release: None,
+                local_label: None,
+            }
+        }
+
+        /// Implements the default PEP 440 rule that `<`/`>` exclude
+        /// pre-releases, unless the candidate is a pre-release of the
+        /// same release as the bound itself.
+        fn allows_prerelease(candidate: &Version, bound: &Version) -> bool {
+            !candidate.is_prerelease() || candidate.release == bound.release
+        }
+
+        pub fn matches(&self, version: &Version) -> bool {
+            match *self {
+                Specifier::Equal { version: ref v, prefix } => {
+                    if prefix {
+                        Self::release_prefix_matches(v, version)
+                    } else {
+                        version == v
+                    }
+                }
+                Specifier::NotEqual { version: ref v, prefix } => {
+                    if prefix {
+                        !Self::release_prefix_matches(v, version)
+                    } else {
+                        version != v
+                    }
+                }
+                Specifier::LessThanEqual(ref v) => version <= v,
+                Specifier::GreaterThanEqual(ref v) => version >= v,
+                Specifier::LessThan(ref v) => version < v && Self::allows_prerelease(version, v),
+                Specifier::GreaterThan(ref v) => version > v && Self::allows_prerelease(version, v),
+                Specifier::Compatible(ref v) => {
+                    version >= v && Self::release_prefix_matches(&Self::compatible_prefix(v), version)
+                }
+                Specifier::ArbitraryEqual(ref s) => format!("{}", version) == *s,
+            }
+        }
+    }
+
+    /// A comma-separated collection of `Specifier` clauses, all of which
+    /// must match for a version to be considered contained.
+    #[derive(Debug)]
+    pub struct SpecifierSet {
+        specifiers: Vec<Specifier>,
+    }
+
+    impl SpecifierSet {
+        pub fn parse(s: &str) -> Result<SpecifierSet> {
+            let specifiers = s.split(',')
+                .map(|clause| Specifier::parse(clause.trim()))
+                .collect::<Result<Vec<_>>>()?;
+
+            Ok(SpecifierSet { specifiers: specifiers })
+        }
+
+        pub fn contains(&self, version: &Version) -> bool {
+            self.specifiers.iter().all(|specifier| specifier.matches(version))
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    mod serde_impl {
+        use super::Version;
+        use std::{fmt, result};
+        use serde::{Serialize, Serializer, Deserialize, Deserializer};
+        use serde::de::{self, Visitor};
+
+        impl Serialize for Version {
+            fn serialize<S>(&self, serializer: S) -> result::Result<S::Ok, S::Error>
+                where S: Serializer
+            {
+                serializer.serialize_str(&self.to_string())
+            }
+        }
+
+        struct VersionVisitor;
+
+        impl<'de> Visitor<'de> for VersionVisitor {
+            type Value = Version;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a PEP 440 version string")
+            }
+
+            fn visit_str<E>(self, v: &str) -> result::Result<Version, E>
+                where E: de::Error
+            {
+                Version::parse(v).map_err(de::Error::custom)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for Version {
+            fn deserialize<D>(deserializer: D) -> result::Result<Version, D::Error>
+                where D: Deserializer<'de>
+            {
+                deserializer.deserialize_str(VersionVisitor)
+            }
+        }
+    }
+
     #[cfg(test)]
     mod tests {
-        use pep440::{Version, PreReleaseSegment};
+        use pep440::{Version, PreReleaseSegment, LocalSegment, SpecifierSet};
 
         #[test]
         fn parse() {
@@ -397,9 +745,15 @@ pub mod pep440 {
         #[test]
         fn normalization_local_version_segments() {
             assert_eq!(Version::parse("1.0+ubuntu-1").unwrap().local_label,
-                       Some(String::from("ubuntu.1")));
+                       Some(vec![LocalSegment::Alpha("ubuntu".to_string()), LocalSegment::Numeric(1)]));
             assert_eq!(Version::parse("1.0+ubuntu_1").unwrap().local_label,
-                       Some(String::from("ubuntu.1")));
+                       Some(vec![LocalSegment::Alpha("ubuntu".to_string()), LocalSegment::Numeric(1)]));
+        }
+
+        #[test]
+        fn normalization_local_version_segments_numeric_overflow() {
+            assert_eq!(Version::parse("1.0+99999999999999999999999999").unwrap().local_label,
+                       Some(vec![LocalSegment::Alpha("99999999999999999999999999".to_string())]));
         }
 
         #[test]
@@ -455,5 +809,173 @@ pub mod pep440 {
             assert_eq!(format!("{}", Version::parse("1!2.3.4a5.post6.dev7+foo.1").unwrap()),
                        "1!2.3.4a5.post6.dev7+foo.1");
         }
+
+        #[test]
+        fn ordering_total_order() {
+            let versions = ["1.0.dev1",
+                             "1.0a1",
+                             "1.0a1.post1.dev1",
+                             "1.0b1",
+                             "1.0rc1",
+                             "1.0",
+                             "1.0+local",
+                             "1.0.post1"];
+
+            for window in versions.windows(2) {
+                let a = Version::parse(window[0]).unwrap();
+                let b = Version::parse(window[1]).unwrap();
+                assert!(a < b, "expected {} < {}", window[0], window[1]);
+            }
+        }
+
+        #[test]
+        fn ordering_release_trailing_zeroes() {
+            assert_eq!(Version::parse("1.0").unwrap(), Version::parse("1.0.0").unwrap());
+            assert!(Version::parse("1.0").unwrap() < Version::parse("1.1").unwrap());
+        }
+
+        #[test]
+        fn ordering_local_version_segments() {
+            assert!(Version::parse("1.0+1").unwrap() > Version::parse("1.0+foo").unwrap());
+            assert!(Version::parse("1.0+foo.bar").unwrap() < Version::parse("1.0+foo.1").unwrap());
+            assert!(Version::parse("1.0+foo").unwrap() < Version::parse("1.0+foo.1").unwrap());
+        }
+
+        #[test]
+        fn specifier_equal() {
+            let set = SpecifierSet::parse("==1.0").unwrap();
+            assert!(set.contains(&Version::parse("1.0").unwrap()));
+            assert!(!set.contains(&Version::parse("1.0.1").unwrap()));
+        }
+
+        #[test]
+        fn specifier_equal_prefix() {
+            let set = SpecifierSet::parse("==1.0.*").unwrap();
+            assert!(set.contains(&Version::parse("1.0").unwrap()));
+            assert!(set.contains(&Version::parse("1.0.1").unwrap()));
+            assert!(set.contains(&Version::parse("1.0.1.post1").unwrap()));
+            assert!(!set.contains(&Version::parse("1.1").unwrap()));
+        }
+
+        #[test]
+        fn specifier_not_equal() {
+            let set = SpecifierSet::parse("!=1.0").unwrap();
+            assert!(!set.contains(&Version::parse("1.0").unwrap()));
+            assert!(set.contains(&Version::parse("1.0.1").unwrap()));
+        }
+
+        #[test]
+        fn specifier_not_equal_prefix() {
+            let set = SpecifierSet::parse("!=1.0.*").unwrap();
+            assert!(!set.contains(&Version::parse("1.0.1").unwrap()));
+            assert!(set.contains(&Version::parse("1.1").unwrap()));
+        }
+
+        #[test]
+        fn specifier_less_than_equal() {
+            let set = SpecifierSet::parse("<=1.0").unwrap();
+            assert!(set.contains(&Version::parse("1.0").unwrap()));
+            assert!(set.contains(&Version::parse("0.9").unwrap()));
+            assert!(!set.contains(&Version::parse("1.0.1").unwrap()));
+        }
+
+        #[test]
+        fn specifier_greater_than_equal() {
+            let set = SpecifierSet::parse(">=1.0").unwrap();
+            assert!(set.contains(&Version::parse("1.0").unwrap()));
+            assert!(set.contains(&Version::parse("1.0.1").unwrap()));
+            assert!(!set.contains(&Version::parse("0.9").unwrap()));
+        }
+
+        #[test]
+        fn specifier_less_than_excludes_prerelease() {
+            let set = SpecifierSet::parse("<1.0").unwrap();
+            assert!(!set.contains(&Version::parse("1.0").unwrap()));
+            assert!(set.contains(&Version::parse("0.9").unwrap()));
+            assert!(!set.contains(&Version::parse("0.9a1").unwrap()));
+        }
+
+        #[test]
+        fn specifier_greater_than_allows_prerelease_of_same_release() {
+            let set = SpecifierSet::parse(">1.0a1").unwrap();
+            assert!(set.contains(&Version::parse("1.0a2").unwrap()));
+            assert!(!set.contains(&Version::parse("0.9").unwrap()));
+        }
+
+        #[test]
+        fn specifier_compatible_release() {
+            let set = SpecifierSet::parse("~=2.2").unwrap();
+            assert!(set.contains(&Version::parse("2.5").unwrap()));
+            assert!(!set.contains(&Version::parse("3.0").unwrap()));
+            assert!(!set.contains(&Version::parse("2.1").unwrap()));
+
+            assert!(super::Specifier::parse("~=2").is_err());
+        }
+
+        #[test]
+        fn specifier_compatible_release_three_segments() {
+            let set = SpecifierSet::parse("~=2.2.1").unwrap();
+            assert!(set.contains(&Version::parse("2.2.5").unwrap()));
+            assert!(!set.contains(&Version::parse("2.3").unwrap()));
+            assert!(!set.contains(&Version::parse("2.2.0").unwrap()));
+        }
+
+        #[test]
+        fn specifier_arbitrary_equal() {
+            let set = SpecifierSet::parse("===1.0").unwrap();
+            assert!(set.contains(&Version::parse("1.0").unwrap()));
+            assert!(!set.contains(&Version::parse("1.0.0").unwrap()));
+        }
+
+        #[test]
+        fn specifier_set_multiple_clauses() {
+            let set = SpecifierSet::parse(">=1.0, <2.0").unwrap();
+            assert!(set.contains(&Version::parse("1.5").unwrap()));
+            assert!(!set.contains(&Version::parse("2.0").unwrap()));
+            assert!(!set.contains(&Version::parse("0.5").unwrap()));
+        }
+
+        #[cfg(feature = "serde")]
+        #[test]
+        fn serde_roundtrip() {
+            let version: Version = ::serde_json::from_str("\"1!2.3.4a5.post6.dev7+foo.1\"")
+                .unwrap();
+            assert_eq!(format!("{}", version), "1!2.3.4a5.post6.dev7+foo.1");
+
+            let json = ::serde_json::to_string(&version).unwrap();
+            assert_eq!(json, "\"1!2.3.4a5.post6.dev7+foo.1\"");
+        }
+
+        #[test]
+        fn is_prerelease() {
+            assert!(Version::parse("1.0a1").unwrap().is_prerelease());
+            assert!(Version::parse("1.0.dev1").unwrap().is_prerelease());
+            assert!(!Version::parse("1.0").unwrap().is_prerelease());
+        }
+
+        #[test]
+        fn is_postrelease() {
+            assert!(Version::parse("1.0.post1").unwrap().is_postrelease());
+            assert!(!Version::parse("1.0").unwrap().is_postrelease());
+        }
+
+        #[test]
+        fn is_devrelease() {
+            assert!(Version::parse("1.0.dev1").unwrap().is_devrelease());
+            assert!(!Version::parse("1.0").unwrap().is_devrelease());
+        }
+
+        #[test]
+        fn public() {
+            assert_eq!(Version::parse("1.0+abc").unwrap().public(), "1.0");
+            assert_eq!(Version::parse("1.0").unwrap().public(), "1.0");
+        }
+
+        #[test]
+        fn base_version() {
+            assert_eq!(Version::parse("1!2.3.4a5.post6.dev7+foo.1").unwrap().base_version(),
+                       "1!2.3.4");
+            assert_eq!(Version::parse("1.0").unwrap().base_version(), "1.0");
+        }
     }
 }